@@ -2,21 +2,26 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use std::{
-    path::Path,
-    time::{SystemTime, UNIX_EPOCH},
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
 use argh::FromArgs;
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use notify::{
-    Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+    Config, Event, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher,
     event::{ModifyKind, RenameMode},
 };
-use rusqlite::{Connection, params};
+use rusqlite::{Connection, Transaction, params};
+use serde::Deserialize;
 use tokio::{
     sync::mpsc,
     time::{Duration, interval},
 };
 use uuid::Uuid;
+use walkdir::WalkDir;
 
 #[derive(FromArgs)]
 /// A file change monitoring tool
@@ -28,6 +33,44 @@ struct Args {
     /// sqlite database path
     #[argh(positional)]
     db_path: String,
+
+    /// debounce window in milliseconds for coalescing bursts of events per path (default: 100)
+    #[argh(option, default = "100")]
+    debounce_ms: u64,
+
+    /// path to an ignore-pattern config file (hjson or json)
+    #[argh(option)]
+    ignore_config: Option<String>,
+
+    /// watcher backend to use: `native` (inotify/FSEvents/etc.) or `poll` (default: native)
+    #[argh(option, default = "Backend::Native")]
+    backend: Backend,
+
+    /// poll interval in milliseconds, only used with `--backend poll` (default: 1000)
+    #[argh(option, default = "1000")]
+    poll_interval_ms: u64,
+}
+
+/// Which notify implementation to watch the tree with.
+#[derive(Clone, Copy)]
+enum Backend {
+    /// Kernel-level notifications (inotify/FSEvents/ReadDirectoryChangesW).
+    Native,
+    /// Periodically re-scans the tree; works on NFS/SMB/overlay/bind-mounted volumes
+    /// where kernel-level notifications don't reach.
+    Poll,
+}
+
+impl std::str::FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "native" => Ok(Backend::Native),
+            "poll" => Ok(Backend::Poll),
+            other => Err(format!("unknown backend `{other}` (expected `native` or `poll`)")),
+        }
+    }
 }
 
 struct EventRecord {
@@ -36,8 +79,65 @@ struct EventRecord {
     change_type: String,
     path: String,
     file_name: String,
+    old_path: Option<String>,
+    new_path: Option<String>,
+    /// "live" for events observed while running, "scan" for startup reconciliation.
+    source: String,
 }
 
+impl EventRecord {
+    fn new(change_type: &str, path: PathBuf) -> Option<Self> {
+        Self::with_source(change_type, path, "live")
+    }
+
+    /// Like `new`, but marks the record as produced by the startup reconciliation scan
+    /// rather than a live filesystem event.
+    fn scan(change_type: &str, path: PathBuf) -> Option<Self> {
+        Self::with_source(change_type, path, "scan")
+    }
+
+    fn with_source(change_type: &str, path: PathBuf, source: &str) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?.to_string();
+        Some(Self {
+            id: Uuid::new_v4().to_string(),
+            timestamp: now_secs(),
+            change_type: change_type.to_string(),
+            path: path.to_str()?.to_string(),
+            file_name,
+            old_path: None,
+            new_path: None,
+            source: source.to_string(),
+        })
+    }
+
+    fn rename(old_path: PathBuf, new_path: PathBuf) -> Option<Self> {
+        let file_name = new_path.file_name()?.to_str()?.to_string();
+        Some(Self {
+            id: Uuid::new_v4().to_string(),
+            timestamp: now_secs(),
+            change_type: "rename".to_string(),
+            path: new_path.to_str()?.to_string(),
+            file_name,
+            old_path: Some(old_path.to_str()?.to_string()),
+            new_path: Some(new_path.to_str()?.to_string()),
+            source: "live".to_string(),
+        })
+    }
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+/// How long an unmatched half of a rename is kept before it's flushed as an orphan.
+const RENAME_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Tracker id (from notify's rename cookie) -> the `From` half still waiting for its `To`.
+type PendingRenames = Arc<Mutex<HashMap<usize, (PathBuf, Instant)>>>;
+
 fn init_db(db_path: &str) -> rusqlite::Result<()> {
     let conn = Connection::open(db_path)?;
     conn.execute_batch(
@@ -48,77 +148,604 @@ fn init_db(db_path: &str) -> rusqlite::Result<()> {
             change_type TEXT NOT NULL,
             path TEXT NOT NULL,
             file_name TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS file_state (
+            path TEXT PRIMARY KEY,
+            mtime INTEGER NOT NULL,
+            size INTEGER NOT NULL,
+            hash TEXT
         );",
     )?;
+    migrate_file_events(&conn)?;
+    Ok(())
+}
+
+/// `CREATE TABLE IF NOT EXISTS` above is a no-op against a `file_events` table left
+/// behind by a pre-rename-tracking binary, so a DB opened from before these columns
+/// existed would otherwise keep its original 5-column shape. Add any of them that are
+/// missing so older databases end up on the current schema instead of failing the
+/// first INSERT in `flush_records`.
+fn migrate_file_events(conn: &Connection) -> rusqlite::Result<()> {
+    let mut existing = HashSet::new();
+    let mut stmt = conn.prepare("PRAGMA table_info(file_events)")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        existing.insert(row.get::<_, String>("name")?);
+    }
+    drop(rows);
+    drop(stmt);
+
+    for (column, ddl) in [
+        ("old_path", "ALTER TABLE file_events ADD COLUMN old_path TEXT"),
+        ("new_path", "ALTER TABLE file_events ADD COLUMN new_path TEXT"),
+        (
+            "source",
+            "ALTER TABLE file_events ADD COLUMN source TEXT NOT NULL DEFAULT 'live'",
+        ),
+    ] {
+        if !existing.contains(column) {
+            conn.execute(ddl, [])?;
+        }
+    }
+
     Ok(())
 }
 
-fn process_event(event: Event) -> Vec<EventRecord> {
-    let change_type = match event.kind {
-        EventKind::Create(_) => "create",
-        EventKind::Modify(kind) => {
-            match kind {
-                // ignore metadata update
-                ModifyKind::Metadata(_) => return vec![],
-                ModifyKind::Name(RenameMode::From) => "rename_from",
-                ModifyKind::Name(RenameMode::To) => "rename_to",
-                ModifyKind::Name(RenameMode::Any) => "renamed",
-                _ => "modify",
+fn process_event(event: Event, pending: &PendingRenames) -> Vec<EventRecord> {
+    match event.kind {
+        EventKind::Create(_) => event
+            .paths
+            .into_iter()
+            .filter_map(|path| EventRecord::new("create", path))
+            .collect(),
+        EventKind::Modify(ModifyKind::Metadata(_)) => vec![],
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            let [old_path, new_path] = match <[PathBuf; 2]>::try_from(event.paths) {
+                Ok(pair) => pair,
+                Err(_) => return vec![],
+            };
+            EventRecord::rename(old_path, new_path)
+                .into_iter()
+                .collect()
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+            let Some(path) = event.paths.into_iter().next() else {
+                return vec![];
+            };
+            match event.attrs.tracker() {
+                Some(tracker) => {
+                    pending
+                        .lock()
+                        .unwrap()
+                        .insert(tracker, (path, Instant::now()));
+                    vec![]
+                }
+                // No tracker cookie to correlate with: fall back to a standalone record.
+                None => EventRecord::new("rename_from", path).into_iter().collect(),
             }
         }
-        EventKind::Remove(_) => "remove",
-        _ => return vec![],
-    };
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+            let Some(path) = event.paths.into_iter().next() else {
+                return vec![];
+            };
+            match event.attrs.tracker() {
+                Some(tracker) => match pending.lock().unwrap().remove(&tracker) {
+                    Some((old_path, _)) => {
+                        EventRecord::rename(old_path, path).into_iter().collect()
+                    }
+                    // The matching From was never seen: treat it as a fresh file.
+                    None => EventRecord::new("create", path).into_iter().collect(),
+                },
+                None => EventRecord::new("rename_to", path).into_iter().collect(),
+            }
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::Any)) => event
+            .paths
+            .into_iter()
+            .filter_map(|path| EventRecord::new("renamed", path))
+            .collect(),
+        EventKind::Modify(_) => event
+            .paths
+            .into_iter()
+            .filter_map(|path| EventRecord::new("modify", path))
+            .collect(),
+        EventKind::Remove(_) => event
+            .paths
+            .into_iter()
+            .filter_map(|path| EventRecord::new("remove", path))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Merge a freshly arrived record into the one already buffered for the same path,
+/// following the precedence ladder for transient sequences within the debounce window.
+/// Returns `None` when the pair cancels out entirely.
+fn merge_event_record(existing: EventRecord, incoming: EventRecord) -> Option<EventRecord> {
+    match (existing.change_type.as_str(), incoming.change_type.as_str()) {
+        // Created and removed again before anyone saw it settle: nothing happened.
+        ("create", "remove") => None,
+        // Still the same file coming into existence; keep reporting it as a create.
+        ("create", "modify") => Some(EventRecord {
+            change_type: "create".to_string(),
+            ..incoming
+        }),
+        // A correlated rename already carries old_path/new_path; don't let a later
+        // event on the new path (e.g. a modify) silently overwrite that correlation.
+        ("rename", _) => Some(existing),
+        // Repeated modifies (or anything else): the latest record wins.
+        _ => Some(incoming),
+    }
+}
+
+/// Buffer events per path and only forward the latest merged record for a path once it
+/// has been quiet for `window`, so a burst of events for the same file becomes one row.
+/// On shutdown, flushes everything buffered right away instead of waiting for the quiet
+/// window, then passes remaining events straight through until `rx` closes.
+async fn debounce_events(
+    mut rx: mpsc::Receiver<EventRecord>,
+    tx: mpsc::Sender<EventRecord>,
+    window: Duration,
+    mut shutdown: tokio::sync::watch::Receiver<bool>,
+) {
+    let mut pending: HashMap<PathBuf, (EventRecord, Instant)> = HashMap::new();
+    let mut tick = interval((window / 4).max(Duration::from_millis(10)));
+    let mut shutting_down = false;
+
+    loop {
+        tokio::select! {
+            _ = tick.tick() => {
+                let ready: Vec<PathBuf> = pending
+                    .iter()
+                    .filter(|(_, (_, seen_at))| seen_at.elapsed() >= window)
+                    .map(|(path, _)| path.clone())
+                    .collect();
+                for path in ready {
+                    if let Some((record, _)) = pending.remove(&path)
+                        && tx.send(record).await.is_err()
+                    {
+                        return;
+                    }
+                }
+            }
+            _ = shutdown.changed(), if !shutting_down => {
+                shutting_down = true;
+                for (_, (record, _)) in pending.drain() {
+                    if tx.send(record).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            maybe_record = rx.recv() => {
+                let Some(record) = maybe_record else {
+                    for (record, _) in pending.into_values() {
+                        let _ = tx.send(record).await;
+                    }
+                    return;
+                };
+
+                if shutting_down {
+                    // No more coalescing once shutdown has begun: forward immediately.
+                    let _ = tx.send(record).await;
+                    continue;
+                }
+
+                let key = PathBuf::from(&record.path);
+                let merged = match pending.remove(&key) {
+                    Some((existing, _)) => merge_event_record(existing, record),
+                    None => Some(record),
+                };
+                if let Some(record) = merged {
+                    pending.insert(key, (record, Instant::now()));
+                }
+            }
+        }
+    }
+}
+
+/// Flush pending renames that have been waiting longer than `RENAME_TIMEOUT` for their
+/// other half, recording them as an orphaned `remove` (the matching `To` moved the file
+/// outside the watched tree).
+fn sweep_stale_renames(pending: &PendingRenames) -> Vec<EventRecord> {
+    let mut pending = pending.lock().unwrap();
+    let stale: Vec<usize> = pending
+        .iter()
+        .filter(|(_, (_, seen_at))| seen_at.elapsed() >= RENAME_TIMEOUT)
+        .map(|(tracker, _)| *tracker)
+        .collect();
+
+    stale
+        .into_iter()
+        .filter_map(|tracker| pending.remove(&tracker))
+        .filter_map(|(path, _)| EventRecord::new("remove", path))
+        .collect()
+}
+
+/// A file's last known mtime/size (and optional content hash), used to detect changes
+/// that happened while the watcher wasn't running.
+struct FileState {
+    mtime: i64,
+    size: i64,
+    hash: Option<String>,
+}
+
+#[cfg(feature = "hashing")]
+fn hash_file(path: &Path) -> Option<String> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let mut hasher = blake3::Hasher::new();
+    std::io::copy(&mut file, &mut hasher).ok()?;
+    Some(hasher.finalize().to_hex().to_string())
+}
+
+#[cfg(not(feature = "hashing"))]
+fn hash_file(_path: &Path) -> Option<String> {
+    None
+}
+
+fn stat_file(path: &Path) -> Option<FileState> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let mtime = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_secs() as i64;
+    Some(FileState {
+        mtime,
+        size: metadata.len() as i64,
+        hash: hash_file(path),
+    })
+}
+
+fn load_file_state(conn: &Connection) -> rusqlite::Result<HashMap<String, FileState>> {
+    conn.prepare("SELECT path, mtime, size, hash FROM file_state")?
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                FileState {
+                    mtime: row.get(1)?,
+                    size: row.get(2)?,
+                    hash: row.get(3)?,
+                },
+            ))
+        })?
+        .collect()
+}
 
-    event
-        .paths
+fn scan_file_state(root_dir: &str, matcher: &IgnoreMatcher) -> HashMap<String, FileState> {
+    WalkDir::new(root_dir)
         .into_iter()
-        .filter_map(|path| {
-            let file_name = path.file_name()?.to_str()?;
-            Some(EventRecord {
-                id: Uuid::new_v4().to_string(),
-                timestamp: SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs() as i64,
-                change_type: change_type.to_string(),
-                path: path.to_str()?.to_string(),
-                file_name: file_name.to_string(),
-            })
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| !matcher.is_ignored(entry.path()))
+        .filter_map(|entry| {
+            let path = entry.path().to_str()?.to_string();
+            let state = stat_file(entry.path())?;
+            Some((path, state))
         })
         .collect()
 }
 
+/// Diff the previous run's `file_state` snapshot against a fresh scan of `root_dir`,
+/// producing synthetic records for everything that changed while the watcher was down.
+fn diff_file_state(
+    previous: &HashMap<String, FileState>,
+    current: &HashMap<String, FileState>,
+) -> Vec<EventRecord> {
+    let mut records = Vec::new();
+
+    for (path, state) in current {
+        match previous.get(path) {
+            None => records.extend(EventRecord::scan("create", PathBuf::from(path))),
+            Some(prev)
+                if prev.mtime != state.mtime || prev.size != state.size || prev.hash != state.hash =>
+            {
+                records.extend(EventRecord::scan("modify", PathBuf::from(path)))
+            }
+            _ => {}
+        }
+    }
+
+    for path in previous.keys() {
+        if !current.contains_key(path) {
+            records.extend(EventRecord::scan("remove", PathBuf::from(path)));
+        }
+    }
+
+    records
+}
+
+fn persist_file_state(conn: &Connection, current: &HashMap<String, FileState>) -> rusqlite::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM file_state", [])?;
+    for (path, state) in current {
+        tx.execute(
+            "INSERT INTO file_state (path, mtime, size, hash) VALUES (?1, ?2, ?3, ?4)",
+            params![path, state.mtime, state.size, state.hash],
+        )?;
+    }
+    tx.commit()
+}
+
+/// Walk `root_dir`, diff it against the `file_state` left by the previous run, and
+/// return the synthetic records needed to bring the database back in sync. Leaves
+/// `file_state` holding the freshly scanned snapshot as the new baseline.
+fn reconcile_on_startup(
+    root_dir: &str,
+    db_path: &str,
+    matcher: &IgnoreMatcher,
+) -> rusqlite::Result<Vec<EventRecord>> {
+    let conn = Connection::open(db_path)?;
+    let previous = load_file_state(&conn)?;
+    let current = scan_file_state(root_dir, matcher);
+    let records = diff_file_state(&previous, &current);
+    persist_file_state(&conn, &current)?;
+    Ok(records)
+}
+
+/// Keep `file_state` in sync with a record the live writer is about to persist, so the
+/// next startup's reconciliation scan has an accurate baseline.
+fn update_file_state(tx: &Transaction, record: &EventRecord) {
+    match record.change_type.as_str() {
+        "remove" => {
+            let _ = tx.execute("DELETE FROM file_state WHERE path = ?1", params![record.path]);
+        }
+        "rename" => {
+            if let Some(old_path) = &record.old_path {
+                let _ = tx.execute("DELETE FROM file_state WHERE path = ?1", params![old_path]);
+            }
+            upsert_file_state(tx, &record.path);
+        }
+        "create" | "modify" => upsert_file_state(tx, &record.path),
+        _ => {}
+    }
+}
+
+fn upsert_file_state(tx: &Transaction, path: &str) {
+    // The startup scan only records regular files; directories stay out of the baseline.
+    if !Path::new(path).is_file() {
+        return;
+    }
+    let Some(state) = stat_file(Path::new(path)) else {
+        return;
+    };
+    let _ = tx.execute(
+        "INSERT INTO file_state (path, mtime, size, hash) VALUES (?1, ?2, ?3, ?4)
+        ON CONFLICT(path) DO UPDATE SET mtime = excluded.mtime, size = excluded.size, hash = excluded.hash",
+        params![path, state.mtime, state.size, state.hash],
+    );
+}
+
+/// Patterns dropped by default so `.git/`, common VCS dirs and dotfiles don't flood the
+/// database; overridden entirely by setting `use_default_ignores: false` in the config.
+const DEFAULT_IGNORE_PATTERNS: &[&str] = &[
+    "**/.git/**",
+    "**/.svn/**",
+    "**/.hg/**",
+    "**/node_modules/**",
+    "**/target/**",
+    "**/.DS_Store",
+    ".*",
+];
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct IgnoreConfig {
+    /// glob/gitignore-style patterns matched against the full path and the file name
+    patterns: Vec<String>,
+    /// if non-empty, only files with one of these extensions are recorded
+    allowed_extensions: Vec<String>,
+    use_default_ignores: bool,
+}
+
+impl Default for IgnoreConfig {
+    fn default() -> Self {
+        Self {
+            patterns: Vec::new(),
+            allowed_extensions: Vec::new(),
+            use_default_ignores: true,
+        }
+    }
+}
+
+struct IgnoreMatcher {
+    globs: GlobSet,
+    allowed_extensions: Option<HashSet<String>>,
+}
+
+impl IgnoreMatcher {
+    fn is_ignored(&self, path: &Path) -> bool {
+        if self.globs.is_match(path) {
+            return true;
+        }
+        if let Some(file_name) = path.file_name().and_then(|f| f.to_str())
+            && self.globs.is_match(file_name)
+        {
+            return true;
+        }
+        if let Some(allowed) = &self.allowed_extensions {
+            let matches_allowed = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|ext| allowed.contains(&ext.to_lowercase()));
+            if !matches_allowed {
+                return true;
+            }
+        }
+        false
+    }
+
+    // `record.path` is always the destination: the path itself for plain events, and
+    // new_path for a rename. A rename is judged on where the file ended up, not where
+    // it came from, so a rename into a watched/allowed path is never suppressed just
+    // because the source side matched an ignore rule.
+    fn is_record_ignored(&self, record: &EventRecord) -> bool {
+        self.is_ignored(Path::new(&record.path))
+    }
+}
+
+/// Build the concrete watcher for the requested backend, boxed behind the `Watcher`
+/// trait so the rest of the pipeline doesn't care which one it got.
+fn build_watcher(
+    backend: Backend,
+    poll_interval: Duration,
+    event_tx: std::sync::mpsc::Sender<notify::Result<Event>>,
+) -> notify::Result<Box<dyn Watcher + Send>> {
+    match backend {
+        Backend::Native => {
+            let watcher = RecommendedWatcher::new(event_tx, Config::default())?;
+            Ok(Box::new(watcher))
+        }
+        Backend::Poll => {
+            let config = Config::default().with_poll_interval(poll_interval);
+            let watcher = PollWatcher::new(event_tx, config)?;
+            Ok(Box::new(watcher))
+        }
+    }
+}
+
+/// Resolves once Ctrl-C or (on Unix) SIGTERM is received, so every task can stop
+/// accepting new work and flush whatever it's already holding.
+async fn wait_for_shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl-C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+fn load_ignore_matcher(
+    config_path: Option<&str>,
+) -> Result<IgnoreMatcher, Box<dyn std::error::Error>> {
+    let config = match config_path {
+        Some(path) => {
+            let contents = std::fs::read_to_string(path)?;
+            deser_hjson::from_str(&contents)?
+        }
+        None => IgnoreConfig::default(),
+    };
+
+    let mut builder = GlobSetBuilder::new();
+    if config.use_default_ignores {
+        for pattern in DEFAULT_IGNORE_PATTERNS {
+            builder.add(Glob::new(pattern)?);
+        }
+    }
+    for pattern in &config.patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+
+    let allowed_extensions = if config.allowed_extensions.is_empty() {
+        None
+    } else {
+        Some(
+            config
+                .allowed_extensions
+                .iter()
+                .map(|ext| ext.to_lowercase())
+                .collect(),
+        )
+    };
+
+    Ok(IgnoreMatcher {
+        globs: builder.build()?,
+        allowed_extensions,
+    })
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Args = argh::from_env();
     init_db(&args.db_path)?;
+    let ignore_matcher = Arc::new(load_ignore_matcher(args.ignore_config.as_deref())?);
 
-    let (tx, mut rx) = mpsc::channel(100);
+    // Reconcile the database against whatever changed on disk while we weren't running,
+    // before the live watcher starts streaming new events.
+    let scan_records = {
+        let root_dir = args.root_dir.clone();
+        let db_path = args.db_path.clone();
+        let matcher = ignore_matcher.clone();
+        tokio::task::spawn_blocking(move || reconcile_on_startup(&root_dir, &db_path, &matcher))
+            .await??
+    };
+    if !scan_records.is_empty() {
+        flush_records(scan_records, &args.db_path).await;
+    }
+
+    let (tx, raw_rx) = mpsc::channel(100);
+    let (debounced_tx, mut rx) = mpsc::channel(100);
     let db_path = args.db_path.clone();
+    let pending_renames: PendingRenames = Arc::new(Mutex::new(HashMap::new()));
+    let debounce_window = Duration::from_millis(args.debounce_ms);
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+
+    tokio::spawn(async move {
+        wait_for_shutdown_signal().await;
+        let _ = shutdown_tx.send(true);
+    });
 
     // File watcher thread
     let root_dir = args.root_dir.clone();
+    let watcher_pending = pending_renames.clone();
+    let watcher_matcher = ignore_matcher.clone();
+    let watcher_shutdown = shutdown_rx.clone();
+    let backend = args.backend;
+    let poll_interval = Duration::from_millis(args.poll_interval_ms);
     tokio::task::spawn_blocking(move || {
         let (sync_tx, sync_rx) = std::sync::mpsc::channel();
-        let mut watcher = RecommendedWatcher::new(sync_tx, Config::default()).unwrap();
+        let mut watcher = build_watcher(backend, poll_interval, sync_tx).unwrap();
 
         watcher
             .watch(Path::new(&root_dir), RecursiveMode::Recursive)
             .unwrap();
 
-        for event in sync_rx.iter().flatten() {
-            let tx = tx.clone();
-            tokio::task::spawn(async move {
-                let events = process_event(event);
-                for record in events {
-                    tx.send(record).await.unwrap();
+        // Processed on this single thread, in arrival order, so a rename's paired
+        // From/To events can never race each other over `watcher_pending`.
+        while !*watcher_shutdown.borrow() {
+            match sync_rx.recv_timeout(Duration::from_millis(200)) {
+                Ok(Ok(event)) => {
+                    for record in process_event(event, &watcher_pending) {
+                        if watcher_matcher.is_record_ignored(&record) {
+                            continue;
+                        }
+                        if tx.blocking_send(record).is_err() {
+                            return;
+                        }
+                    }
                 }
-            });
+                Ok(Err(_)) => {}
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
+                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+            }
         }
     });
 
+    // Debounce layer: coalesces bursts of events for the same path
+    let debounce_shutdown = shutdown_rx.clone();
+    tokio::spawn(debounce_events(
+        raw_rx,
+        debounced_tx,
+        debounce_window,
+        debounce_shutdown,
+    ));
+
     // Database writer task
+    let mut writer_shutdown = shutdown_rx;
     tokio::spawn(async move {
         let mut buffer = Vec::with_capacity(100);
         let mut interval = interval(Duration::from_secs(2));
@@ -126,6 +753,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         loop {
             tokio::select! {
                 _ = interval.tick() => {
+                    buffer.extend(sweep_stale_renames(&pending_renames));
                     if !buffer.is_empty() {
                         let tmp = buffer;
                         buffer = Vec::new();
@@ -141,6 +769,27 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         buffer.clear();
                     }
                 }
+                _ = writer_shutdown.changed() => {
+                    // The watcher thread stops producing and the debounce layer flushes
+                    // and closes its channel; drain whatever that sends us before the
+                    // final flush so nothing buffered anywhere is lost.
+                    while let Some(record) = rx.recv().await {
+                        buffer.push(record);
+                    }
+                    // The watcher has exited, so any rename From still waiting for its
+                    // To will never be matched: flush it as an orphaned remove.
+                    buffer.extend(
+                        pending_renames
+                            .lock()
+                            .unwrap()
+                            .drain()
+                            .filter_map(|(_, (path, _))| EventRecord::new("remove", path)),
+                    );
+                    if !buffer.is_empty() {
+                        flush_records(buffer, &db_path).await;
+                    }
+                    break;
+                }
             }
         }
     })
@@ -158,17 +807,21 @@ async fn flush_records(records: Vec<EventRecord>, db_path: &str) {
 
         for record in records {
             tx.execute(
-                "INSERT INTO file_events (id, timestamp, change_type, path, file_name) 
-                VALUES (?1, ?2, ?3, ?4, ?5)",
+                "INSERT INTO file_events (id, timestamp, change_type, path, file_name, old_path, new_path, source)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
                 params![
                     record.id,
                     record.timestamp,
                     record.change_type,
                     record.path,
-                    record.file_name
+                    record.file_name,
+                    record.old_path,
+                    record.new_path,
+                    record.source,
                 ],
             )
             .unwrap();
+            update_file_state(&tx, &record);
         }
 
         tx.commit().unwrap();